@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// The body grafana's simplejson datasource POSTs to `/search`
+#[derive(Debug, Deserialize)]
+pub struct Search {
+    pub target: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse(pub Vec<String>);
+
+#[derive(Debug, Deserialize)]
+pub struct Range {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Target {
+    pub target: String,
+    #[serde(rename = "refId")]
+    pub ref_id: String,
+    #[serde(rename = "type")]
+    pub _type: String,
+}
+
+/// The body grafana's simplejson datasource POSTs to `/query`
+#[derive(Debug, Deserialize)]
+pub struct Query {
+    #[serde(rename = "panelId")]
+    pub panel_id: i64,
+    pub range: Range,
+    pub interval: String,
+    #[serde(rename = "intervalMs")]
+    pub interval_ms: i64,
+    pub targets: Vec<Target>,
+    pub format: String,
+    #[serde(rename = "maxDataPoints")]
+    pub max_data_points: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Series {
+    pub target: String,
+    pub datapoints: Vec<[u64; 2]>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Column {
+    pub text: String,
+    #[serde(rename = "type")]
+    pub _type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Table {
+    #[serde(rename = "type")]
+    pub _type: String,
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Grafana's simplejson datasource tells series and tables apart by shape, not by an explicit
+/// tag, so we serialize untagged and let `target`/`datapoints` vs `columns`/`rows` do the talking.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum TargetData {
+    Series(Series),
+    Table(Table),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryResponse(pub Vec<TargetData>);
+
+/// The part of grafana's `/annotations` POST body describing the annotation source being queried.
+/// None of these fields affect the response, but they need to round-trip through deserialization.
+#[derive(Debug, Deserialize)]
+pub struct AnnotationQuery {
+    pub name: String,
+    pub enable: bool,
+    pub query: Option<String>,
+}
+
+/// The body grafana's simplejson datasource POSTs to `/annotations`
+#[derive(Debug, Deserialize)]
+pub struct AnnotationRequest {
+    pub range: Range,
+    pub annotation: AnnotationQuery,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Annotation {
+    pub time: u64,
+    pub title: String,
+    pub text: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnnotationResponse(pub Vec<Annotation>);
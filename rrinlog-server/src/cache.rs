@@ -0,0 +1,134 @@
+use crate::api::QueryResponse;
+use chrono::{DateTime, Utc};
+use linked_hash_map::LinkedHashMap;
+use parking_lot::RwLock;
+use std::time::{Duration, Instant};
+
+/// Identifies a `/query` result independent of how the request arrived. `range.raw` (eg.
+/// `now-1h`) is deliberately excluded -- only the resolved absolute `from`/`to` that grafana sends
+/// alongside it affect the SQL we run, so two requests for the same absolute window hit the same
+/// entry even if one was phrased as a relative range.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub target: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub interval_ms: i64,
+    pub ip: String,
+}
+
+struct Entry {
+    response: QueryResponse,
+    inserted: Instant,
+}
+
+/// A bounded, insertion-ordered cache of recent `/query` results. Grafana dashboards re-issue the
+/// same target/range/interval on every panel refresh, so serving those repeats out of memory
+/// avoids hammering the underlying SQLite file. Entries are evicted oldest-first once `capacity`
+/// is exceeded, and read stale once older than `ttl`.
+pub struct QueryCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: RwLock<LinkedHashMap<CacheKey, Entry>>,
+}
+
+impl QueryCache {
+    pub fn new(capacity: usize, ttl: Duration) -> QueryCache {
+        QueryCache {
+            capacity,
+            ttl,
+            entries: RwLock::new(LinkedHashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<QueryResponse> {
+        let entries = self.entries.read();
+        entries
+            .get(key)
+            .filter(|entry| entry.inserted.elapsed() < self.ttl)
+            .map(|entry| entry.response.clone())
+    }
+
+    /// Errors are never cached -- callers should only insert the `Ok` half of a query result, so
+    /// a transient `DbQuery`/`DbConn` failure doesn't get served back to every panel for the rest
+    /// of the TTL.
+    pub fn insert(&self, key: CacheKey, response: QueryResponse) {
+        let mut entries = self.entries.write();
+        entries.insert(
+            key,
+            Entry {
+                response,
+                inserted: Instant::now(),
+            },
+        );
+
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{Series, TargetData};
+    use chrono::TimeZone;
+    use std::thread;
+
+    fn make_key(target: &str) -> CacheKey {
+        CacheKey {
+            target: target.to_string(),
+            from: Utc.ymd(2020, 1, 1).and_hms(0, 0, 0),
+            to: Utc.ymd(2020, 1, 1).and_hms(1, 0, 0),
+            interval_ms: 30_000,
+            ip: "127.0.0.1".to_string(),
+        }
+    }
+
+    fn make_response() -> QueryResponse {
+        QueryResponse(vec![TargetData::Series(Series {
+            target: "sites".to_string(),
+            datapoints: vec![[1, 0]],
+        })])
+    }
+
+    #[test]
+    fn get_returns_inserted_value() {
+        let cache = QueryCache::new(10, Duration::from_secs(30));
+        cache.insert(make_key("sites"), make_response());
+
+        assert!(cache.get(&make_key("sites")).is_some());
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let cache = QueryCache::new(10, Duration::from_millis(10));
+        cache.insert(make_key("sites"), make_response());
+        assert!(cache.get(&make_key("sites")).is_some());
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(cache.get(&make_key("sites")).is_none());
+    }
+
+    #[test]
+    fn oldest_entry_evicted_past_capacity() {
+        let cache = QueryCache::new(2, Duration::from_secs(30));
+        cache.insert(make_key("a"), make_response());
+        cache.insert(make_key("b"), make_response());
+        cache.insert(make_key("c"), make_response());
+
+        assert!(cache.get(&make_key("a")).is_none());
+        assert!(cache.get(&make_key("b")).is_some());
+        assert!(cache.get(&make_key("c")).is_some());
+    }
+
+    #[test]
+    fn error_paths_never_populate_the_cache() {
+        // `insert` only accepts a `QueryResponse`, never a `Result` -- a caller that hits a
+        // `DataError` has no way to cache it and simply skips `insert`, so a later request for
+        // the same key still misses instead of being served a stale failure.
+        let cache = QueryCache::new(10, Duration::from_secs(30));
+
+        assert!(cache.get(&make_key("sites")).is_none());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::api::Range;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text, Timestamp};
+use uom::si::i64::Time;
+use uom::si::time::second;
+
+#[derive(QueryableByName, Debug)]
+pub struct SiteRow {
+    #[sql_type = "Text"]
+    pub host: String,
+    #[sql_type = "BigInt"]
+    pub ep: i64,
+    #[sql_type = "BigInt"]
+    pub views: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct OutboundRow {
+    #[sql_type = "BigInt"]
+    pub ep: i64,
+    #[sql_type = "BigInt"]
+    pub bytes: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct BlogRow {
+    #[sql_type = "Text"]
+    pub referer: String,
+    #[sql_type = "BigInt"]
+    pub views: i64,
+}
+
+/// Number of hits per host, bucketed into `interval` sized windows between `range.from` and
+/// `range.to`. The epoch returned for each row is in milliseconds, as that's what grafana expects
+/// datapoints to be keyed by.
+pub fn sites(conn: &SqliteConnection, range: &Range, interval: Time) -> QueryResult<Vec<SiteRow>> {
+    let secs = interval.get::<second>();
+    diesel::sql_query(
+        "SELECT host, \
+                (strftime('%s', time) / ?) * ? * 1000 AS ep, \
+                COUNT(*) AS views \
+         FROM log \
+         WHERE time BETWEEN ? AND ? AND host IS NOT NULL \
+         GROUP BY host, ep",
+    )
+    .bind::<BigInt, _>(secs)
+    .bind::<BigInt, _>(secs)
+    .bind::<Timestamp, _>(range.from.naive_utc())
+    .bind::<Timestamp, _>(range.to.naive_utc())
+    .load(conn)
+}
+
+/// Bytes sent to `ip` per `interval` sized bucket between `range.from` and `range.to`.
+pub fn outbound_data(
+    conn: &SqliteConnection,
+    range: &Range,
+    ip: &str,
+    interval: Time,
+) -> QueryResult<Vec<OutboundRow>> {
+    let secs = interval.get::<second>();
+    diesel::sql_query(
+        "SELECT (strftime('%s', time) / ?) * ? * 1000 AS ep, \
+                SUM(bytes) AS bytes \
+         FROM log \
+         WHERE time BETWEEN ? AND ? AND remote_addr = ? \
+         GROUP BY ep",
+    )
+    .bind::<BigInt, _>(secs)
+    .bind::<BigInt, _>(secs)
+    .bind::<Timestamp, _>(range.from.naive_utc())
+    .bind::<Timestamp, _>(range.to.naive_utc())
+    .bind::<Text, _>(ip)
+    .load(conn)
+}
+
+/// Number of hits per referer for requests directed at blog articles between `range.from` and
+/// `range.to`.
+pub fn blog_posts(conn: &SqliteConnection, range: &Range, ip: &str) -> QueryResult<Vec<BlogRow>> {
+    diesel::sql_query(
+        "SELECT referer, COUNT(*) AS views \
+         FROM log \
+         WHERE time BETWEEN ? AND ? AND remote_addr = ? AND path LIKE '/blog/%' \
+         GROUP BY referer \
+         ORDER BY views DESC",
+    )
+    .bind::<Timestamp, _>(range.from.naive_utc())
+    .bind::<Timestamp, _>(range.to.naive_utc())
+    .bind::<Text, _>(ip)
+    .load(conn)
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct AnnotationRow {
+    #[sql_type = "Timestamp"]
+    pub time: NaiveDateTime,
+    #[sql_type = "Text"]
+    pub path: String,
+    #[sql_type = "BigInt"]
+    pub status: i64,
+}
+
+/// HTTP 5xx responses between `range.from` and `range.to`, for overlaying as grafana annotations
+/// on top of the `sites`/`outbound_data` timeseries.
+pub fn annotations(conn: &SqliteConnection, range: &Range) -> QueryResult<Vec<AnnotationRow>> {
+    diesel::sql_query(
+        "SELECT time, path, status \
+         FROM log \
+         WHERE time BETWEEN ? AND ? AND status >= 500 \
+         ORDER BY time",
+    )
+    .bind::<Timestamp, _>(range.from.naive_utc())
+    .bind::<Timestamp, _>(range.to.naive_utc())
+    .load(conn)
+}
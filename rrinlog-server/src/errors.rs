@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use diesel::r2d2;
+
+/// Errors that can bubble out of the `/query` handler and are turned into an HTTP response by
+/// `actix-web`'s `failure::Error` -> `Responder` glue.
+#[derive(Fail, Debug)]
+pub enum DataError {
+    #[fail(display = "expected at least one target, found {}", _0)]
+    OneTarget(usize),
+
+    #[fail(display = "from date ({}) is after to date ({})", _0, _1)]
+    DatesSwapped(DateTime<Utc>, DateTime<Utc>),
+
+    #[fail(display = "unable to acquire connection to {}: {}", _0, _1)]
+    DbConn(String, r2d2::PoolError),
+
+    #[fail(display = "{} query failed: {}", _0, _1)]
+    DbQuery(String, diesel::result::Error),
+
+    #[fail(display = "unrecognized target: {}", _0)]
+    UnrecognizedTarget(String),
+}
+
+impl DataError {
+    /// A short, low-cardinality label suitable for a prometheus metric -- never the dynamic value
+    /// carried inside a variant.
+    pub fn metric_kind(&self) -> &'static str {
+        match self {
+            DataError::OneTarget(_) => "one_target",
+            DataError::DatesSwapped(_, _) => "dates_swapped",
+            DataError::DbConn(_, _) => "db_conn",
+            DataError::DbQuery(_, _) => "db_query",
+            DataError::UnrecognizedTarget(_) => "unrecognized_target",
+        }
+    }
+}
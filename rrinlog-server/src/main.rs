@@ -1,3 +1,4 @@
+extern crate actix_service;
 extern crate actix_web;
 extern crate chrono;
 #[macro_use]
@@ -5,9 +6,14 @@ extern crate diesel;
 extern crate env_logger;
 #[macro_use]
 extern crate failure;
+extern crate futures;
+extern crate governor;
+extern crate hashbrown;
 extern crate itertools;
 #[macro_use]
 extern crate log;
+extern crate parking_lot;
+extern crate prometheus;
 extern crate rrinlog_core;
 extern crate serde;
 #[macro_use]
@@ -19,42 +25,107 @@ extern crate structopt;
 extern crate uom;
 
 mod api;
+mod cache;
 mod dao;
 mod errors;
+mod metrics;
 mod options;
+mod ratelimit;
 
 use actix_web::middleware::Logger;
 use actix_web::web::{self, Data, Json};
-use actix_web::{App, HttpServer, Responder};
+use actix_web::{App, HttpResponse, HttpServer, Responder};
 use api::*;
+use cache::{CacheKey, QueryCache};
 use chrono::prelude::*;
 use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool};
 use env_logger::{Builder, Target};
 use errors::DataError;
 use failure::Error;
+use hashbrown::HashMap;
 use itertools::Itertools;
+use metrics::Metrics;
+use ratelimit::{RateLimit, RateLimiters};
 use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
 use structopt::StructOpt;
 use uom::si::i64::*;
 use uom::si::time::{millisecond, second};
 
+type DbPool = Pool<ConnectionManager<SqliteConnection>>;
+type Conn = diesel::r2d2::PooledConnection<ConnectionManager<SqliteConnection>>;
+
+/// Sets sqlite's `busy_timeout` on every connection as the pool opens it, so a query
+/// that runs into a locked database file gives up instead of blocking the actix worker forever.
+#[derive(Debug)]
+struct ConnectionOptions {
+    query_timeout: Duration,
+}
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.execute(&format!(
+            "PRAGMA busy_timeout = {};",
+            self.query_timeout.as_millis()
+        ))
+        .map_err(diesel::r2d2::Error::QueryError)?;
+        Ok(())
+    }
+}
+
 macro_rules! create_app {
     ($opts:expr) => {{
         App::new()
             .data($opts)
             .wrap(Logger::default())
+            .wrap(RateLimit)
             .route("/", web::to(index))
             .route("/search", web::post().to(search))
             .route("/query", web::post().to(query))
+            .route("/annotations", web::post().to(annotations_route))
+            .route("/metrics", web::get().to(metrics_route))
     }};
 }
 
-#[derive(Debug, Clone)]
-struct RinState {
-    pub db: String,
+/// One of potentially several rotated nginx log databases we query and merge results across.
+#[derive(Clone)]
+struct RotatedDb {
+    pub path: String,
+    pub pool: DbPool,
+}
+
+#[derive(Clone)]
+pub(crate) struct RinState {
+    pub dbs: Vec<RotatedDb>,
+    pub cache: Arc<QueryCache>,
+    pub metrics: Arc<Metrics>,
+    pub rate_limiters: Arc<RateLimiters>,
     pub ip: String,
 }
 
+/// Caches one pooled connection per rotated db for the lifetime of a single `/query` call, so that
+/// dispatching several targets against the same db reuses the connection instead of taking a
+/// fresh one from the pool per target.
+#[derive(Default)]
+struct ConnCache {
+    conns: HashMap<String, Conn>,
+}
+
+impl ConnCache {
+    fn get(&mut self, db: &RotatedDb) -> Result<&Conn, DataError> {
+        if !self.conns.contains_key(&db.path) {
+            let conn = db
+                .pool
+                .get()
+                .map_err(|e| DataError::DbConn(db.path.clone(), e))?;
+            self.conns.insert(db.path.clone(), conn);
+        }
+        Ok(&self.conns[&db.path])
+    }
+}
+
 fn index() -> impl Responder {
     "Hello world!"
 }
@@ -65,25 +136,24 @@ fn search(data: Json<Search>) -> impl Responder {
         "blog_hits".to_string(),
         "sites".to_string(),
         "outbound_data".to_string(),
+        "5xx_errors".to_string(),
     ]))
 }
 
+fn metrics_route(opt: Data<RinState>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(opt.metrics.gather())
+}
+
 fn query(query: Json<Query>, opt: Data<RinState>) -> Result<Json<QueryResponse>, Error> {
     debug!("Search received: {:?}", query);
 
-    // Acquire SQLite connection on each request. This can be considered inefficient, but since
-    // there isn't a roundtrip connection cost the benefit to debugging of never having a stale
-    // connection is well worth it.
-    let conn = SqliteConnection::establish(&opt.db)
-        .map_err(|e| DataError::DbConn(opt.db.to_owned(), e))?;
-
-    // Grafana can technically ask for more than one target at once. It can ask for "blog_hits" and
-    // "sites" in one request, but we're going to keep it simply and work with only with requests
-    // that ask for one set of data.
-    let first = query
-        .targets
-        .first()
-        .ok_or_else(|| DataError::OneTarget(query.targets.len()))?;
+    // Grafana legitimately sends several targets in one POST, eg. "blog_hits" and "sites" for a
+    // dashboard that mixes a table panel with a timeseries panel.
+    if query.targets.is_empty() {
+        return Err(DataError::OneTarget(0).into());
+    }
 
     // Our code assumes that `from < to` in calculations for vector sizes. Else resizing the vector
     // will underflow and panic
@@ -97,33 +167,197 @@ fn query(query: Json<Query>, opt: Data<RinState>) -> Result<Json<QueryResponse>,
     // should never trust user input)
     let interval: Time = Time::new::<second>(std::cmp::max(query.interval_ms / 1000, 1));
 
-    let result = match first.target.as_str() {
-        "blog_hits" => get_blog_posts(&conn, &query, &opt),
-        "sites" => get_sites(&conn, &query, interval),
-        "outbound_data" => get_outbound(&conn, &query, &opt, interval),
+    let mut data = Vec::new();
+    let mut conns = ConnCache::default();
+
+    for target in &query.targets {
+        opt.metrics
+            .requests_total
+            .with_label_values(&[&target.target])
+            .inc();
+
+        let key = CacheKey {
+            target: target.target.clone(),
+            from: query.range.from,
+            to: query.range.to,
+            interval_ms: query.interval_ms,
+            ip: opt.ip.clone(),
+        };
+
+        if let Some(cached) = opt.cache.get(&key) {
+            data.extend(cached.0);
+            continue;
+        }
+
+        let result = run_target(&target.target, &query, &opt, interval, &mut conns);
+        let result = result.map_err(|e| {
+            if let Some(data_err) = e.as_fail().downcast_ref::<DataError>() {
+                opt.metrics
+                    .query_errors_total
+                    .with_label_values(&[&target.target, data_err.metric_kind()])
+                    .inc();
+            }
+            e
+        })?;
+
+        opt.cache.insert(key, result.clone());
+        data.extend(result.0);
+    }
+
+    Ok(Json(QueryResponse(data)))
+}
+
+fn run_target(
+    target: &str,
+    query: &Query,
+    opt: &RinState,
+    interval: Time,
+    conns: &mut ConnCache,
+) -> Result<QueryResponse, Error> {
+    let timer = opt
+        .metrics
+        .query_duration_seconds
+        .with_label_values(&[target])
+        .start_timer();
+
+    let result = match target {
+        "blog_hits" => get_blog_posts(opt, query, conns),
+        "sites" => get_sites(opt, query, interval, conns),
+        "outbound_data" => get_outbound(opt, query, interval, conns),
         x => Err(DataError::UnrecognizedTarget(String::from(x)).into()),
     };
 
-    Ok(Json(result?))
+    timer.observe_duration();
+
+    result
+}
+
+fn annotations_route(
+    req: Json<AnnotationRequest>,
+    opt: Data<RinState>,
+) -> Result<Json<AnnotationResponse>, Error> {
+    debug!("Annotations received: {:?}", req);
+
+    if req.range.from > req.range.to {
+        return Err(DataError::DatesSwapped(req.range.from, req.range.to).into());
+    }
+
+    let mut rows = Vec::new();
+
+    for db in &opt.dbs {
+        if !covers(&db.path, &req.range) {
+            continue;
+        }
+
+        let conn = match db.pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("{}", DataError::DbConn(db.path.clone(), e));
+                continue;
+            }
+        };
+
+        match dao::annotations(&conn, &req.range) {
+            Ok(r) => rows.extend(r),
+            Err(e) => warn!(
+                "{}",
+                DataError::DbQuery(format!("annotations ({})", db.path), e)
+            ),
+        }
+    }
+
+    rows.sort_unstable_by_key(|r| r.time);
+
+    let annotations = rows
+        .into_iter()
+        .map(|r| Annotation {
+            time: (r.time.timestamp() as u64) * 1000,
+            title: "5xx".to_string(),
+            text: format!("{} returned {}", r.path, r.status),
+            tags: vec!["5xx".to_string()],
+        })
+        .collect();
+
+    Ok(Json(AnnotationResponse(annotations)))
+}
+
+/// A rotated database is only worth querying if its data could overlap `range` at all. We don't
+/// track each file's write span, so we use its mtime as a cheap proxy for "last time this file was
+/// written to" -- a db modified before `range.from` can't contain anything newer than that. If we
+/// can't even stat the file, we don't skip it; we let the caller's connection attempt fail and log
+/// a warning instead, rather than silently excluding a database that might just have odd
+/// permissions.
+fn covers(path: &str, range: &Range) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|modified| DateTime::<Utc>::from(modified) >= range.from)
+        .unwrap_or(true)
 }
 
 fn get_sites(
-    conn: &SqliteConnection,
+    opt: &RinState,
     data: &Query,
     interval: Time,
+    conns: &mut ConnCache,
 ) -> Result<QueryResponse, Error> {
-    let mut rows = dao::sites(conn, &data.range, interval)
-        .map_err(|e| DataError::DbQuery("sites".to_string(), e))?;
+    let mut merged: HashMap<(String, i64), i64> = HashMap::new();
+    let mut any_ok = false;
+    let mut last_err = None;
+
+    for db in &opt.dbs {
+        if !covers(&db.path, &data.range) {
+            continue;
+        }
+
+        let conn = match conns.get(db) {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("{}", e);
+                last_err = Some(e);
+                continue;
+            }
+        };
+
+        match dao::sites(conn, &data.range, interval) {
+            Ok(rows) => {
+                any_ok = true;
+                for row in rows {
+                    *merged.entry((row.host, row.ep)).or_insert(0) += row.views;
+                }
+            }
+            Err(e) => {
+                let err = DataError::DbQuery(format!("sites ({})", db.path), e);
+                warn!("{}", err);
+                last_err = Some(err);
+            }
+        }
+    }
+
+    // A db simply not covering this range (or there being no dbs configured yet) is normal and
+    // yields an empty series; an actual connection/query failure on every db we tried is not, and
+    // should fail the request rather than silently return an empty one.
+    if !any_ok {
+        if let Some(err) = last_err {
+            return Err(err.into());
+        }
+    }
+
+    let mut rows: Vec<(String, i64, i64)> = merged
+        .into_iter()
+        .map(|((host, ep), views)| (host, ep, views))
+        .collect();
 
     // Just like python, in order to group by host, we need to have the vector sorted by host. We
     // include sorting by epoch time as grafana expects time to be sorted
     // TODO: Is there someway to sort by string without having to clone?
-    rows.sort_unstable_by_key(|x| (x.host.clone(), x.ep));
+    rows.sort_unstable_by_key(|x| (x.0.clone(), x.1));
 
     let mut v = Vec::new();
-    for (host, points) in &rows.into_iter().group_by(|x| x.host.clone()) {
+    for (host, points) in &rows.into_iter().group_by(|x| x.0.clone()) {
         // points is a sparse array of the number of views seen at a given epoch ms.
-        let p: Vec<_> = points.map(|x| [x.views as u64, x.ep as u64]).collect();
+        let p: Vec<_> = points
+            .map(|(_, ep, views)| [views as u64, ep as u64])
+            .collect();
         let datapoints = fill_datapoints(&data.range, interval, &p);
 
         v.push(TargetData::Series(Series {
@@ -160,15 +394,54 @@ fn fill_datapoints(range: &Range, interval: Time, points: &[[u64; 2]]) -> Vec<[u
 }
 
 fn get_outbound(
-    conn: &SqliteConnection,
-    data: &Query,
     opt: &RinState,
+    data: &Query,
     interval: Time,
+    conns: &mut ConnCache,
 ) -> Result<QueryResponse, Error> {
-    let rows = dao::outbound_data(conn, &data.range, &opt.ip, interval)
-        .map_err(|e| DataError::DbQuery("outbound data".to_string(), e))?;
+    let mut merged: HashMap<i64, i64> = HashMap::new();
+    let mut any_ok = false;
+    let mut last_err = None;
+
+    for db in &opt.dbs {
+        if !covers(&db.path, &data.range) {
+            continue;
+        }
+
+        let conn = match conns.get(db) {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("{}", e);
+                last_err = Some(e);
+                continue;
+            }
+        };
+
+        match dao::outbound_data(conn, &data.range, &opt.ip, interval) {
+            Ok(rows) => {
+                any_ok = true;
+                for row in rows {
+                    *merged.entry(row.ep).or_insert(0) += row.bytes;
+                }
+            }
+            Err(e) => {
+                let err = DataError::DbQuery(format!("outbound data ({})", db.path), e);
+                warn!("{}", err);
+                last_err = Some(err);
+            }
+        }
+    }
 
-    let p: Vec<_> = rows.iter().map(|x| [x.bytes as u64, x.ep as u64]).collect();
+    if !any_ok {
+        if let Some(err) = last_err {
+            return Err(err.into());
+        }
+    }
+
+    let p: Vec<_> = merged
+        .into_iter()
+        .map(|(ep, bytes)| [bytes as u64, ep as u64])
+        .collect();
     let datapoints = fill_datapoints(&data.range, interval, &p);
 
     let elem = TargetData::Series(Series {
@@ -180,17 +453,56 @@ fn get_outbound(
 }
 
 fn get_blog_posts(
-    conn: &SqliteConnection,
-    data: &Query,
     opt: &RinState,
+    data: &Query,
+    conns: &mut ConnCache,
 ) -> Result<QueryResponse, Error> {
-    let rows = dao::blog_posts(conn, &data.range, &opt.ip)
-        .map_err(|e| DataError::DbQuery("blog posts".to_string(), e))?;
+    let mut merged: HashMap<String, i64> = HashMap::new();
+    let mut any_ok = false;
+    let mut last_err = None;
+
+    for db in &opt.dbs {
+        if !covers(&db.path, &data.range) {
+            continue;
+        }
+
+        let conn = match conns.get(db) {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("{}", e);
+                last_err = Some(e);
+                continue;
+            }
+        };
+
+        match dao::blog_posts(conn, &data.range, &opt.ip) {
+            Ok(rows) => {
+                any_ok = true;
+                for row in rows {
+                    *merged.entry(row.referer).or_insert(0) += row.views;
+                }
+            }
+            Err(e) => {
+                let err = DataError::DbQuery(format!("blog posts ({})", db.path), e);
+                warn!("{}", err);
+                last_err = Some(err);
+            }
+        }
+    }
+
+    if !any_ok {
+        if let Some(err) = last_err {
+            return Err(err.into());
+        }
+    }
+
+    let mut referers: Vec<(String, i64)> = merged.into_iter().collect();
+    referers.sort_unstable_by(|a, b| b.1.cmp(&a.1));
 
     // Grafana expects rows to contain heterogeneous values in the same order as the table columns.
-    let r: Vec<_> = rows
+    let r: Vec<_> = referers
         .into_iter()
-        .map(|x| vec![json!(x.referer), json!(x.views)])
+        .map(|(referer, views)| vec![json!(referer), json!(views)])
         .collect();
 
     Ok(QueryResponse(vec![TargetData::Table(create_blog_table(r))]))
@@ -228,14 +540,51 @@ fn init_logging() -> Result<(), log::SetLoggerError> {
         .try_init()
 }
 
+fn build_pool(opts: &options::Opt, path: &str) -> DbPool {
+    let manager = ConnectionManager::<SqliteConnection>::new(path);
+
+    Pool::builder()
+        .connection_timeout(Duration::from_millis(opts.connect_timeout_ms))
+        .connection_customizer(Box::new(ConnectionOptions {
+            query_timeout: Duration::from_millis(opts.query_timeout_ms),
+        }))
+        .build_unchecked(manager)
+}
+
+fn build_dbs(opts: &options::Opt) -> Vec<RotatedDb> {
+    opts.dbs
+        .iter()
+        .map(|path| RotatedDb {
+            path: path.to_owned(),
+            pool: build_pool(opts, path),
+        })
+        .collect()
+}
+
 fn main() -> std::io::Result<()> {
     init_logging().expect("Logging to initialize");
     let opts = options::Opt::from_args();
+    let dbs = build_dbs(&opts);
+    let cache = Arc::new(QueryCache::new(
+        opts.cache_capacity,
+        Duration::from_secs(opts.cache_ttl_secs),
+    ));
+    let metrics = Arc::new(Metrics::new());
+    let rate_limiters = Arc::new(RateLimiters::new(
+        opts.rate_limit_rps,
+        opts.rate_limit_burst,
+        opts.rate_limit_default_rps,
+        opts.rate_limit_default_burst,
+        opts.rate_limit_default_ip_capacity,
+    ));
     let (addr, state) = {
         (
             opts.addr,
             RinState {
-                db: opts.db,
+                dbs,
+                cache,
+                metrics,
+                rate_limiters,
                 ip: opts.ip,
             },
         )
@@ -296,10 +645,45 @@ mod tests {
     }
 
     fn create_test_server() -> actix_http_test::TestServerRuntime {
-        actix_http_test::TestServer::new(|| {
-            actix_http::HttpService::new(create_app!(RinState {
-                db: "../test-assets/test-access.db".to_string(),
+        create_test_server_with_dbs(vec!["../test-assets/test-access.db".to_string()])
+    }
+
+    fn create_test_server_with_dbs(dbs: Vec<String>) -> actix_http_test::TestServerRuntime {
+        actix_http_test::TestServer::new(move || {
+            let opts = options::Opt {
+                addr: "127.0.0.1:8000".parse().unwrap(),
+                dbs: dbs.clone(),
                 ip: "127.0.0.2".to_string(),
+                connect_timeout_ms: 500,
+                query_timeout_ms: 5000,
+                cache_capacity: 128,
+                cache_ttl_secs: 30,
+                rate_limit_rps: 5,
+                rate_limit_burst: 10,
+                rate_limit_default_rps: 2,
+                rate_limit_default_burst: 4,
+                rate_limit_default_ip_capacity: 10_000,
+            };
+            let dbs = build_dbs(&opts);
+            let cache = Arc::new(QueryCache::new(
+                opts.cache_capacity,
+                Duration::from_secs(opts.cache_ttl_secs),
+            ));
+            let metrics = Arc::new(Metrics::new());
+            let rate_limiters = Arc::new(RateLimiters::new(
+                opts.rate_limit_rps,
+                opts.rate_limit_burst,
+                opts.rate_limit_default_rps,
+                opts.rate_limit_default_burst,
+                opts.rate_limit_default_ip_capacity,
+            ));
+
+            actix_http::HttpService::new(create_app!(RinState {
+                dbs,
+                cache,
+                metrics,
+                rate_limiters,
+                ip: opts.ip,
             }))
         })
     }
@@ -317,6 +701,82 @@ mod tests {
         assert_eq!(str::from_utf8(&bytes).unwrap(), "Hello world!");
     }
 
+    #[test]
+    fn test_metrics_results() {
+        let mut srv = create_test_server();
+        let request = srv.get("/metrics");
+        let mut response = srv.block_on(request.send()).unwrap();
+
+        assert!(response.status().is_success());
+
+        let bytes = srv.block_on(response.body()).unwrap();
+        let body = str::from_utf8(&bytes).unwrap();
+        assert!(body.contains("rrinlog_query_requests_total"));
+        assert!(body.contains("rrinlog_query_errors_total"));
+        assert!(body.contains("rrinlog_query_duration_seconds"));
+    }
+
+    // One of the two configured dbs doesn't exist, but the other does -- the request should
+    // still succeed with results merged from whichever db(s) answered, per the `any_ok`/`last_err`
+    // handling in get_sites/get_outbound/get_blog_posts.
+    #[test]
+    fn test_query_sites_survives_one_unreachable_db() {
+        let mut srv = create_test_server_with_dbs(vec![
+            "../test-assets/test-access.db".to_string(),
+            "/nonexistent/directory/bogus.db".to_string(),
+        ]);
+        let request = srv
+            .post("/query")
+            .header(header::CONTENT_TYPE, "application/json")
+            .send_body(
+                r#"
+{
+  "panelId": 1,
+  "range": {
+    "from": "2017-11-14T13:00:00.866Z",
+    "to": "2017-11-14T14:00:00.866Z",
+    "raw": {
+      "from": "now-1h",
+      "to": "now"
+    }
+  },
+  "rangeRaw": {
+    "from": "now-1h",
+    "to": "now"
+  },
+  "interval": "30s",
+  "intervalMs": 30000,
+  "targets": [
+     { "target": "sites", "refId": "A", "type": "table" }
+  ],
+  "format": "json",
+  "maxDataPoints": 550
+}
+"#,
+            );
+
+        let response = srv.block_on(request).unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.content_type(), "application/json");
+    }
+
+    // The test server's rate_limit_default_burst is 4, so the 5th rapid request from the same
+    // peer (no `X-Api-Key`) should be turned away before it ever reaches the handler.
+    #[test]
+    fn test_rate_limit_429_after_burst() {
+        let mut srv = create_test_server();
+
+        let mut statuses = Vec::new();
+        for _ in 0..5 {
+            let request = srv.get("/");
+            let response = srv.block_on(request.send()).unwrap();
+            statuses.push(response.status());
+        }
+
+        assert!(statuses[..4].iter().all(|s| s.is_success()));
+        assert_eq!(statuses[4], actix_web::http::StatusCode::TOO_MANY_REQUESTS);
+    }
+
     #[test]
     fn test_search_results() {
         let mut srv = create_test_server();
@@ -331,12 +791,41 @@ mod tests {
         assert_eq!(response.content_type(), "application/json");
 
         let bytes = srv.block_on(response.body()).unwrap();
+        // Keep this list in lockstep with `search()`'s `SearchResponse` -- a name added there with
+        // no update here is exactly the kind of mismatch that should fail the suite, not ship.
         assert_eq!(
             str::from_utf8(&bytes).unwrap(),
-            r#"["blog_hits","sites","outbound_data"]"#
+            r#"["blog_hits","sites","outbound_data","5xx_errors"]"#
         );
     }
 
+    #[test]
+    fn test_annotations_results() {
+        let mut srv = create_test_server();
+        let request = srv
+            .post("/annotations")
+            .header(header::CONTENT_TYPE, "application/json")
+            .send_body(
+                r#"
+{
+  "range": {
+    "from": "2017-11-14T13:00:00.866Z",
+    "to": "2017-11-14T14:00:00.866Z"
+  },
+  "annotation": {
+    "name": "5xx_errors",
+    "enable": true,
+    "query": null
+  }
+}
+"#,
+            );
+
+        let response = srv.block_on(request).unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.content_type(), "application/json");
+    }
+
     #[test]
     fn test_query_blog_results() {
         let mut srv = create_test_server();
@@ -375,6 +864,62 @@ mod tests {
         assert_eq!(response.content_type(), "application/json");
     }
 
+    // Grafana sends several targets in one POST for mixed dashboards; each should be dispatched
+    // and concatenated into the response rather than only the first being handled.
+    #[test]
+    fn test_query_multiple_targets_results() {
+        let mut srv = create_test_server();
+        let request = srv
+            .post("/query")
+            .header(header::CONTENT_TYPE, "application/json")
+            .send_body(
+                r#"
+{
+  "panelId": 1,
+  "range": {
+    "from": "2017-11-14T13:00:00.866Z",
+    "to": "2017-11-14T14:00:00.866Z",
+    "raw": {
+      "from": "now-1h",
+      "to": "now"
+    }
+  },
+  "rangeRaw": {
+    "from": "now-1h",
+    "to": "now"
+  },
+  "interval": "30s",
+  "intervalMs": 30000,
+  "targets": [
+     { "target": "sites", "refId": "A", "type": "table" },
+     { "target": "blog_hits", "refId": "B", "type": "table" }
+  ],
+  "format": "json",
+  "maxDataPoints": 550
+}
+"#,
+            );
+
+        let mut response = srv.block_on(request).unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.content_type(), "application/json");
+
+        let bytes = srv.block_on(response.body()).unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let results = body.as_array().expect("response to be a json array");
+
+        assert!(
+            results.iter().any(|r| r.get("datapoints").is_some()),
+            "expected a Series (sites) entry in {:?}",
+            results
+        );
+        assert!(
+            results.iter().any(|r| r.get("columns").is_some()),
+            "expected a Table (blog_hits) entry in {:?}",
+            results
+        );
+    }
+
     #[test]
     fn test_query_sites_results() {
         let mut srv = create_test_server();
@@ -0,0 +1,79 @@
+use prometheus::{
+    histogram_opts, opts, Encoder, HistogramVec, IntCounterVec, Registry, TextEncoder,
+};
+
+/// Prometheus collectors for the `/query` handler, served in text format off `/metrics`. Kept as
+/// one long-lived struct (rather than lazy_static globals) so it can live in `RinState` and be
+/// shared across actix workers the same way the pool and cache are.
+pub struct Metrics {
+    registry: Registry,
+    pub requests_total: IntCounterVec,
+    pub query_errors_total: IntCounterVec,
+    pub query_duration_seconds: HistogramVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = Registry::new();
+
+        let requests_total = IntCounterVec::new(
+            opts!(
+                "rrinlog_query_requests_total",
+                "Total number of /query requests, per target"
+            ),
+            &["target"],
+        )
+        .expect("requests_total to be a valid metric");
+
+        let query_errors_total = IntCounterVec::new(
+            opts!(
+                "rrinlog_query_errors_total",
+                "Total number of /query errors, per target and DataError variant"
+            ),
+            &["target", "kind"],
+        )
+        .expect("query_errors_total to be a valid metric");
+
+        let query_duration_seconds = HistogramVec::new(
+            histogram_opts!(
+                "rrinlog_query_duration_seconds",
+                "Time spent computing an uncached target (acquiring connections, SQLite queries, merging and gap filling), in seconds"
+            ),
+            &["target"],
+        )
+        .expect("query_duration_seconds to be a valid metric");
+
+        registry
+            .register(Box::new(requests_total.clone()))
+            .expect("requests_total to register");
+        registry
+            .register(Box::new(query_errors_total.clone()))
+            .expect("query_errors_total to register");
+        registry
+            .register(Box::new(query_duration_seconds.clone()))
+            .expect("query_duration_seconds to register");
+
+        Metrics {
+            registry,
+            requests_total,
+            query_errors_total,
+            query_duration_seconds,
+        }
+    }
+
+    /// Render every registered collector in Prometheus text exposition format.
+    pub fn gather(&self) -> Vec<u8> {
+        let metric_families = self.registry.gather();
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&metric_families, &mut buffer)
+            .expect("metric families to encode");
+        buffer
+    }
+}
@@ -0,0 +1,58 @@
+use std::net::SocketAddr;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(name = "rrinlog-server")]
+pub struct Opt {
+    /// Address to bind the http server to
+    #[structopt(long = "addr", default_value = "127.0.0.1:8000")]
+    pub addr: SocketAddr,
+
+    /// Paths to the sqlite databases populated by rrinlog-core. Accepts more than one so that
+    /// rotated nginx logs (eg. access.db, access.db.1, ...) can be queried as if they were one
+    /// contiguous database; results from each are merged per target.
+    #[structopt(long = "db", required = true, min_values = 1)]
+    pub dbs: Vec<String>,
+
+    /// IP address that is considered "us" when computing outbound data / blog hits
+    #[structopt(long = "ip")]
+    pub ip: String,
+
+    /// How long to wait for a free pooled connection before giving up
+    #[structopt(long = "connect-timeout-ms", default_value = "500")]
+    pub connect_timeout_ms: u64,
+
+    /// How long a query may wait on a locked database file (mapped to sqlite's busy_timeout)
+    /// before it fails; a query that holds the lock and keeps running is not interrupted
+    #[structopt(long = "query-timeout-ms", default_value = "5000")]
+    pub query_timeout_ms: u64,
+
+    /// Maximum number of distinct query results to keep cached at once
+    #[structopt(long = "cache-capacity", default_value = "128")]
+    pub cache_capacity: usize,
+
+    /// How long a cached query result remains valid before it's recomputed
+    #[structopt(long = "cache-ttl-secs", default_value = "30")]
+    pub cache_ttl_secs: u64,
+
+    /// Requests per second allowed for a single `X-Api-Key`
+    #[structopt(long = "rate-limit-rps", default_value = "5")]
+    pub rate_limit_rps: u32,
+
+    /// Burst size allowed for a single `X-Api-Key` on top of its steady rate
+    #[structopt(long = "rate-limit-burst", default_value = "10")]
+    pub rate_limit_burst: u32,
+
+    /// Requests per second allowed for a single caller IP that sends no `X-Api-Key`
+    #[structopt(long = "rate-limit-default-rps", default_value = "2")]
+    pub rate_limit_default_rps: u32,
+
+    /// Burst size allowed for a single caller IP that sends no `X-Api-Key`
+    #[structopt(long = "rate-limit-default-burst", default_value = "4")]
+    pub rate_limit_default_burst: u32,
+
+    /// Maximum number of distinct anonymous (keyless) caller IPs to track buckets for at once,
+    /// evicted oldest-first
+    #[structopt(long = "rate-limit-default-ip-capacity", default_value = "10000")]
+    pub rate_limit_default_ip_capacity: usize,
+}
@@ -0,0 +1,215 @@
+use crate::RinState;
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{Error as ActixError, HttpResponse};
+use futures::future::{ok, FutureResult};
+use futures::{Future, Poll};
+use governor::clock::DefaultClock;
+use governor::state::{InMemoryState, NotKeyed};
+use governor::{Quota, RateLimiter as GovernorLimiter};
+use hashbrown::HashMap;
+use linked_hash_map::LinkedHashMap;
+use parking_lot::RwLock;
+use std::num::NonZeroU32;
+
+type Limiter = GovernorLimiter<NotKeyed, InMemoryState, DefaultClock>;
+
+fn nonzero(n: u32) -> NonZeroU32 {
+    NonZeroU32::new(n).unwrap_or_else(|| NonZeroU32::new(1).unwrap())
+}
+
+/// Per-API-key and per-anonymous-IP token bucket quotas, applied to every route of the app (not
+/// only `/query`). Each distinct `X-Api-Key` gets its own `rps`/`burst` bucket, created lazily on
+/// first use; any key value is accepted, keys are not checked against a set of issued
+/// credentials, and the table of key buckets has no size bound. Callers with no key are bucketed
+/// by peer IP instead of sharing one global bucket, each at the distinct (typically tighter)
+/// `default_rps`/`default_burst` quota -- so one anonymous scraper hammering the endpoint only
+/// exhausts its own bucket, not every anonymous Grafana viewer's. Unlike the key table, the IP
+/// table is capped at `default_ip_capacity` and evicted oldest-first, the same way
+/// `cache::QueryCache` bounds its own insertion-ordered map.
+pub struct RateLimiters {
+    rps: NonZeroU32,
+    burst: NonZeroU32,
+    default_rps: NonZeroU32,
+    default_burst: NonZeroU32,
+    default_ip_capacity: usize,
+    limiters: RwLock<HashMap<String, Limiter>>,
+    default_limiters: RwLock<LinkedHashMap<String, Limiter>>,
+}
+
+impl RateLimiters {
+    pub fn new(
+        rps: u32,
+        burst: u32,
+        default_rps: u32,
+        default_burst: u32,
+        default_ip_capacity: usize,
+    ) -> RateLimiters {
+        RateLimiters {
+            rps: nonzero(rps),
+            burst: nonzero(burst),
+            default_rps: nonzero(default_rps),
+            default_burst: nonzero(default_burst),
+            default_ip_capacity,
+            limiters: RwLock::new(HashMap::new()),
+            default_limiters: RwLock::new(LinkedHashMap::new()),
+        }
+    }
+
+    /// Returns `true` if the caller -- identified by `key` (an `X-Api-Key` value) when present, or
+    /// by `ip` otherwise -- is within quota and the request should proceed.
+    pub fn check(&self, key: Option<&str>, ip: &str) -> bool {
+        match key {
+            Some(k) => {
+                if let Some(limiter) = self.limiters.read().get(k) {
+                    return limiter.check().is_ok();
+                }
+
+                let mut limiters = self.limiters.write();
+                let limiter = limiters.entry(k.to_string()).or_insert_with(|| {
+                    GovernorLimiter::direct(Quota::per_second(self.rps).allow_burst(self.burst))
+                });
+                limiter.check().is_ok()
+            }
+            None => {
+                if let Some(limiter) = self.default_limiters.read().get(ip) {
+                    return limiter.check().is_ok();
+                }
+
+                let mut limiters = self.default_limiters.write();
+
+                // Another request for the same ip may have raced us for the write lock above.
+                if !limiters.contains_key(ip) {
+                    limiters.insert(
+                        ip.to_string(),
+                        GovernorLimiter::direct(
+                            Quota::per_second(self.default_rps).allow_burst(self.default_burst),
+                        ),
+                    );
+                }
+
+                let allowed = limiters
+                    .get(ip)
+                    .map(|limiter| limiter.check().is_ok())
+                    .unwrap_or(true);
+
+                while limiters.len() > self.default_ip_capacity {
+                    limiters.pop_front();
+                }
+
+                allowed
+            }
+        }
+    }
+}
+
+/// Short-circuits with `429 Too Many Requests` before a request reaches `query()`, keyed off the
+/// `X-Api-Key` header (falling back to the caller's peer IP when absent). Wired into
+/// `create_app!` alongside `Logger`.
+pub struct RateLimit;
+
+impl<S, B> Transform<S> for RateLimit
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = ActixError>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = FutureResult<Self::Transform, Self::InitError>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimitMiddleware { service })
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RateLimitMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = ActixError>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = ActixError;
+    type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.service.poll_ready()
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let key = req
+            .headers()
+            .get("X-Api-Key")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let ip = req
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let allowed = req
+            .app_data::<RinState>()
+            .map(|state| state.rate_limiters.check(key.as_deref(), &ip))
+            .unwrap_or(true);
+
+        if allowed {
+            Box::new(self.service.call(req))
+        } else {
+            Box::new(ok(req.into_response(
+                HttpResponse::TooManyRequests().finish().into_body(),
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anonymous_caller_is_throttled_past_burst() {
+        let limiters = RateLimiters::new(5, 10, 1, 2, 100);
+
+        assert!(limiters.check(None, "1.2.3.4"));
+        assert!(limiters.check(None, "1.2.3.4"));
+        assert!(!limiters.check(None, "1.2.3.4"));
+    }
+
+    #[test]
+    fn distinct_api_keys_get_independent_buckets() {
+        let limiters = RateLimiters::new(1, 2, 1, 2, 100);
+
+        assert!(limiters.check(Some("key-a"), "1.2.3.4"));
+        assert!(limiters.check(Some("key-a"), "1.2.3.4"));
+        assert!(!limiters.check(Some("key-a"), "1.2.3.4"));
+
+        // key-b has never been seen before, so it gets a fresh bucket rather than sharing
+        // key-a's exhausted one.
+        assert!(limiters.check(Some("key-b"), "1.2.3.4"));
+    }
+
+    #[test]
+    fn anonymous_ip_table_evicts_oldest_past_capacity() {
+        let limiters = RateLimiters::new(5, 10, 1, 1, 2);
+
+        assert!(limiters.check(None, "1.1.1.1"));
+        assert!(!limiters.check(None, "1.1.1.1"));
+
+        assert!(limiters.check(None, "2.2.2.2"));
+        assert!(limiters.check(None, "3.3.3.3"));
+
+        // With capacity 2, tracking a third distinct ip evicts the oldest ("1.1.1.1"), so its
+        // bucket comes back fresh on the next request rather than still being exhausted.
+        assert!(limiters.check(None, "1.1.1.1"));
+    }
+}